@@ -1,14 +1,53 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::Read;
 use std::io::{BufRead, BufReader, Write};
-use std::net::TcpListener;
-use std::sync::Arc;
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use flate2::write::GzEncoder;
+use std::time::{Duration, UNIX_EPOCH};
+use flate2::write::{GzEncoder, ZlibEncoder};
 use flate2::Compression;
 
+/// How long a connection may sit idle waiting for the next request before
+/// we give up on it and let the handler thread return.
+const KEEP_ALIVE_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Content codings we can produce, in server preference order. This order
+/// is also the tie-breaker when a client's `Accept-Encoding` assigns two
+/// codings the same quality value.
+const SUPPORTED_ENCODINGS: [(&str, Encoding); 2] =
+    [("gzip", Encoding::Gzip), ("deflate", Encoding::Deflate)];
+
+/// Caps how much request body (plain or chunk-decoded) we'll buffer in
+/// memory for a single request.
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// Extension -> `Content-Type` lookup for static files served from `--directory`.
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "application/javascript"),
+    ("json", "application/json"),
+    ("txt", "text/plain"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("svg", "image/svg+xml"),
+    ("pdf", "application/pdf"),
+];
+
+/// The fixed GUID RFC 6455 has clients/servers append to the
+/// `Sec-WebSocket-Key` before hashing, to prove both sides speak the
+/// WebSocket protocol (and not some other thing that happens to look like
+/// an HTTP upgrade).
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
 fn main() -> Result<(), ()> {
     let args: Vec<String> = std::env::args().collect();
 
@@ -17,21 +56,31 @@ fn main() -> Result<(), ()> {
         .position(|arg| arg == "--directory")
         .and_then(|pos| args.get(pos + 1));
 
-    let http_server = CodeCraftsHttpServer::new(directory);
+    let threads = args
+        .iter()
+        .position(|arg| arg == "--threads")
+        .and_then(|pos| args.get(pos + 1))
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let http_server = CodeCraftsHttpServer::new(directory, threads);
 
     http_server.start()
 }
 
 struct CodeCraftsHttpServer {
     server: Arc<HttpServer>,
+    threads: usize,
 }
 
 impl CodeCraftsHttpServer {
-    fn new(file_dir: Option<&String>) -> Self {
+    fn new(file_dir: Option<&String>, threads: usize) -> Self {
         Self {
             server: Arc::new(HttpServer {
                 file_dir: file_dir.cloned(),
             }),
+            threads,
         }
     }
 
@@ -40,14 +89,15 @@ impl CodeCraftsHttpServer {
             .context("Failed to bind to address")
             .map_err(|err| {
                 eprintln!("{:?}", err);
-                ()
             })?;
 
+        let pool = ThreadPool::new(self.threads);
+
         for stream in listener.incoming() {
             match stream {
                 Ok(tcp_stream) => {
                     let server = Arc::clone(&self.server);
-                    thread::spawn(move || {
+                    pool.execute(move || {
                         if let Err(err) = server
                             .handle_connection(tcp_stream)
                             .context("Failed to handle connection")
@@ -66,78 +116,243 @@ impl CodeCraftsHttpServer {
     }
 }
 
+/// A fixed-size pool of worker threads fed by a shared job queue, so a burst
+/// of connections can't spawn unbounded threads the way `thread::spawn` per
+/// connection did. Long-lived (keep-alive/WebSocket) connections simply
+/// occupy a worker for their lifetime, same as they would their own thread.
+struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+impl ThreadPool {
+    /// `size` must be at least 1.
+    fn new(size: usize) -> Self {
+        assert!(size > 0);
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..size)
+            .map(|id| Worker::new(id, Arc::clone(&receiver)))
+            .collect();
+
+        Self {
+            workers,
+            sender: Some(sender),
+        }
+    }
+
+    /// Queues `job` for a worker to run. If every worker thread has already
+    /// exited, the channel has no live receiver and the job is simply
+    /// dropped; we log rather than panic the accept loop over it. (A
+    /// panicking job does not end its worker — see `Worker::new`.)
+    fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // The sender is only ever `None` after `drop`, which can't happen
+        // while `self` is still alive to receive this call.
+        if self.sender.as_ref().unwrap().send(Box::new(job)).is_err() {
+            eprintln!("thread pool has no live workers left, dropping job");
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Dropping the sender closes the channel, so each worker's `recv`
+        // returns `Err` once it's drained any queued jobs, and its loop
+        // below exits.
+        drop(self.sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                if thread.join().is_err() {
+                    eprintln!("worker {} panicked", worker.id);
+                }
+            }
+        }
+    }
+}
+
+struct Worker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Self {
+        let thread = thread::spawn(move || loop {
+            let job = receiver.lock().expect("job queue lock poisoned").recv();
+            match job {
+                // Catch a panicking job so one bad request can't retire this
+                // worker (and, eventually, the whole pool).
+                Ok(job) => {
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        eprintln!("worker {id} job panicked");
+                    }
+                }
+                Err(_) => break,
+            }
+        });
+
+        Self {
+            id,
+            thread: Some(thread),
+        }
+    }
+}
+
 struct HttpServer {
     file_dir: Option<String>,
 }
 
 impl HttpServer {
-    fn handle_connection(&self, mut tcp_stream: std::net::TcpStream) -> Result<()> {
+    fn handle_connection(&self, tcp_stream: TcpStream) -> Result<()> {
+        tcp_stream
+            .set_read_timeout(Some(KEEP_ALIVE_READ_TIMEOUT))
+            .context("Failed to set read timeout")?;
+
         let mut reader = BufReader::new(&tcp_stream);
-        let mut request_lines = Vec::new();
-        let mut content_length = 0;
 
+        // Keep servicing requests on this socket until the client (or we)
+        // decide the connection should close.
         loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).context("Failed to read line")?;
-            if line.trim().is_empty() {
-                break;
+            let mut start_line = String::new();
+            let bytes_read = match reader.read_line(&mut start_line) {
+                Ok(n) => n,
+                Err(err) if Self::is_timeout(&err) => return Ok(()),
+                Err(err) => return Err(err).context("Failed to read request line"),
+            };
+            if bytes_read == 0 {
+                // Client closed the connection.
+                return Ok(());
             }
-            if line.starts_with("Content-Length:") {
-                let parts: Vec<&str> = line.split(':').collect();
-                if parts.len() == 2 {
-                    content_length = parts[1].trim().parse().context("Invalid Content-Length")?;
-                }
+
+            let (mut response, keep_alive) =
+                match HttpServer::parse_request(&start_line, &mut reader) {
+                    Ok(request) => {
+                        let keep_alive = Self::should_keep_alive(&request);
+
+                        let path_vec = request.path.split('/').collect::<Vec<&str>>();
+                        let path_parts = path_vec.as_slice();
+
+                        if path_parts == ["", "ws"] && matches!(request.method, Method::Get) {
+                            return self.handle_websocket(&request, &tcp_stream, &mut reader);
+                        }
+
+                        let encoding = Self::negotiate_encoding(request.header("accept-encoding"));
+                        let response = match path_parts {
+                            ["", ""] => self.handle_root_request(&request),
+                            ["", "user-agent"] => self.handle_user_agent_request(&request),
+                            ["", "echo", echo_str] => {
+                                let mut resp = self.handle_echo_request(&request, echo_str);
+                                resp.encoding = encoding;
+                                resp
+                            },
+                            ["", "files", rest @ ..] => {
+                                let mut resp = self.handle_file_request(&request, &rest.join("/"));
+                                resp.encoding = encoding;
+                                resp
+                            },
+                            _ => self.handle_not_found(&request),
+                        };
+                        (response, keep_alive)
+                    }
+                    Err(e) => {
+                        println!("failed to parse request: {:?}", e);
+                        // We can't trust our place in the byte stream after a
+                        // parse failure, so don't try to keep the connection open.
+                        (self.handle_bad_request(), false)
+                    }
+                };
+
+            response.keep_alive = keep_alive;
+
+            // Written through `&TcpStream` (not `tcp_stream` directly) since
+            // `reader` holds a borrow of `tcp_stream` for the lifetime of
+            // this loop.
+            (&tcp_stream)
+                .write_all(&response.to_bytes())
+                .context("Failed to write response")?;
+
+            if !keep_alive {
+                return Ok(());
             }
-            request_lines.extend_from_slice(line.as_bytes());
         }
+    }
 
-        if content_length > 0 {
-            let mut body = vec![0; content_length];
-            reader
-                .read_exact(&mut body)
-                .context("Failed to read body")?;
-            request_lines.extend_from_slice(&body);
-        }
-
-        let response: Response = match HttpServer::parse_request(std::str::from_utf8(&request_lines)?) {
-            Ok(request) => {
-                let supports_gzip = request.headers.get("accept-encoding")
-                    .map(|encodings| encodings.to_lowercase().contains("gzip"))
-                    .unwrap_or(false);
-
-                let path_vec = request.path.split('/').collect::<Vec<&str>>();
-                let path_parts = path_vec.as_slice();
-                match path_parts {
-                    ["", ""] => self.handle_root_request(&request),
-                    ["", "user-agent"] => self.handle_user_agent_request(&request),
-                    ["", "echo", echo_str] => {
-                        let mut resp = self.handle_echo_request(&request, echo_str);
-                        if supports_gzip {
-                            resp.should_compress = true;
-                        }
-                        resp
-                    },
-                    ["", "files", filename] => {
-                        let mut resp = self.handle_file_request(&request, filename);
-                        if supports_gzip {
-                            resp.should_compress = true;
-                        }
-                        resp
-                    },
-                    _ => self.handle_not_found(&request),
-                }
+    /// Per RFC 7230 §6.3: HTTP/1.1 defaults to persistent connections unless
+    /// `Connection: close` is present; HTTP/1.0 defaults to closing unless
+    /// `Connection: keep-alive` is present. Header values are compared
+    /// case-insensitively.
+    fn should_keep_alive(request: &Request) -> bool {
+        let connection = request.header("connection").map(|v| v.to_lowercase());
+        match connection.as_deref() {
+            Some("close") => false,
+            Some("keep-alive") => true,
+            _ => matches!(request.version, Version::Http1_1 | Version::Http2_0),
+        }
+    }
+
+    fn is_timeout(err: &std::io::Error) -> bool {
+        matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        )
+    }
+
+    /// Picks the best content coding we can produce for the given
+    /// `Accept-Encoding` header, or `None` (identity) if nothing we
+    /// support was acceptable.
+    fn negotiate_encoding(accept_encoding: Option<&str>) -> Option<Encoding> {
+        let offers = Self::parse_accept_encoding(accept_encoding?);
+
+        let mut best: Option<(Encoding, f32)> = None;
+        for (name, encoding) in SUPPORTED_ENCODINGS {
+            let q = Self::quality_for(&offers, name).unwrap_or(0.0);
+            if q <= 0.0 {
+                continue;
             }
-            Err(e) => {
-                println!("failed to parse request: {:?}", e);
-                self.handle_internal_server_error()
+            if best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((encoding, q));
             }
-        };
+        }
 
-        tcp_stream
-            .write_all(response.to_http_string().as_bytes())
-            .context("Failed to write response")?;
+        best.map(|(encoding, _)| encoding)
+    }
 
-        Ok(())
+    /// Splits `Accept-Encoding` into `(coding, q)` pairs, e.g.
+    /// `"gzip;q=0.8, deflate, br;q=0"` -> `[("gzip", 0.8), ("deflate", 1.0), ("br", 0.0)]`.
+    fn parse_accept_encoding(header: &str) -> Vec<(String, f32)> {
+        header
+            .split(',')
+            .filter_map(|offer| {
+                let mut params = offer.split(';');
+                let coding = params.next()?.trim().to_lowercase();
+                if coding.is_empty() {
+                    return None;
+                }
+                let q = params
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|value| value.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((coding, q))
+            })
+            .collect()
+    }
+
+    /// An exact match for `name` always wins over the `*` wildcard, even if
+    /// the wildcard appears first in the header or has a higher `q`.
+    fn quality_for(offers: &[(String, f32)], name: &str) -> Option<f32> {
+        offers
+            .iter()
+            .find(|(coding, _)| coding == name)
+            .or_else(|| offers.iter().find(|(coding, _)| coding == "*"))
+            .map(|(_, q)| *q)
     }
 
     fn handle_root_request(&self, request: &Request) -> Response {
@@ -146,12 +361,13 @@ impl HttpServer {
             headers: HashMap::new(),
             status_code: 200,
             version: request.version,
-            should_compress: false,
+            encoding: None,
+            keep_alive: true,
         }
     }
 
     fn handle_user_agent_request(&self, request: &Request) -> Response {
-        let ua = request.headers.get("user-agent").cloned();
+        let ua = request.header("user-agent").map(str::to_string);
         let mut resp_headers = HashMap::new();
         resp_headers.insert("Content-Type".into(), "text/plain".into());
         resp_headers.insert(
@@ -164,13 +380,14 @@ impl HttpServer {
             status_code: 200,
             version: request.version,
             headers: resp_headers,
-            body: ua,
-            should_compress: false,
+            body: ua.map(String::into_bytes),
+            encoding: None,
+            keep_alive: true,
         }
     }
 
     fn handle_echo_request(&self, request: &Request, echo_str: &str) -> Response {
-        let body = echo_str.to_string();
+        let body = echo_str.as_bytes().to_vec();
         let mut resp_headers = HashMap::new();
         resp_headers.insert("Content-Type".into(), "text/plain".into());
         resp_headers.insert("Content-Length".into(), body.len().to_string());
@@ -180,164 +397,692 @@ impl HttpServer {
             version: request.version,
             headers: resp_headers,
             body: Some(body),
-            should_compress: false,
+            encoding: None,
+            keep_alive: true,
         }
     }
 
-    fn handle_file_request(&self, request: &Request, filename: &str) -> Response {
+    /// `relative_path` is everything after `/files/`, still percent-encoded
+    /// and not yet checked against `file_dir`.
+    fn handle_file_request(&self, request: &Request, relative_path: &str) -> Response {
         println!("handling request {:?}", request);
-        if let Some(file_dir) = &self.file_dir {
-            let file_path = format!("{}/{}", file_dir, filename);
-
-            let mut resp_headers = HashMap::<String, String>::new();
-            resp_headers.insert("Content-Type".into(), "application/octet-stream".into());
-
-            match request.method {
-                Method::Get => {
-                    let file_content = match File::open(&file_path) {
-                        Ok(mut file) => {
-                            let mut content = String::new();
-                            file.read_to_string(&mut content)
-                                .context("Failed to read file")
-                                .ok()
-                                .map(|_| content)
-                        }
-                        Err(_) => None,
-                    };
-
-                    if let Some(body) = file_content {
-                        resp_headers.insert("Content-Length".into(), body.len().to_string());
-                        Response {
-                            status_code: 200,
-                            version: request.version,
-                            headers: resp_headers,
-                            body: Some(body),
-                            should_compress: false,
-                        }
-                    } else {
-                        Response {
-                            status_code: 404,
-                            version: request.version,
-                            headers: HashMap::new(),
-                            body: None,
-                            should_compress: false,
-                        }
-                    }
+        let Some(file_dir) = &self.file_dir else {
+            return self.handle_not_found(request);
+        };
+
+        let decoded_path = Self::percent_decode(relative_path);
+        let Some(safe_relative_path) = Self::normalize_relative_path(&decoded_path) else {
+            eprintln!("rejected path escaping file_dir: {:?}", relative_path);
+            return self.handle_forbidden(request);
+        };
+
+        let file_path = if safe_relative_path.is_empty() {
+            PathBuf::from(file_dir)
+        } else {
+            Path::new(file_dir).join(&safe_relative_path)
+        };
+
+        if Self::resolves_outside_root(file_dir, &file_path) {
+            eprintln!("rejected path escaping file_dir: {:?}", relative_path);
+            return self.handle_forbidden(request);
+        }
+
+        match request.method {
+            Method::Get => Self::handle_file_get(request, &file_path),
+            Method::Post => Self::handle_file_post(request, &file_path),
+            _ => {
+                eprintln!("unhandled request method");
+                Response {
+                    body: None,
+                    status_code: 500,
+                    version: request.version,
+                    headers: HashMap::new(),
+                    encoding: None,
+                    keep_alive: true,
                 }
-                Method::Post => {
-                    if let Some(body) = &request.body {
-                        if let Ok(mut file) =
-                            File::create(file_path).context("Failed to create file")
-                        {
-                            if let Ok(_) = file
-                                .write_all(body.as_bytes())
-                                .context("Failed to write to file")
-                            {
-                                Response {
-                                    status_code: 201,
-                                    version: request.version,
-                                    headers: resp_headers,
-                                    body: Some(body.clone()),
-                                    should_compress: false,
-                                }
-                            } else {
-                                eprintln!("failed to write file");
-                                Response {
-                                    status_code: 500,
-                                    version: request.version,
-                                    headers: HashMap::new(),
-                                    body: None,
-                                    should_compress: false,
-                                }
-                            }
-                        } else {
-                            eprintln!("failed to create file");
-                            Response {
-                                status_code: 500,
-                                version: request.version,
-                                headers: HashMap::new(),
-                                body: None,
-                                should_compress: false,
-                            }
-                        }
-                    } else {
-                        Response {
-                            status_code: 500,
-                            version: request.version,
-                            headers: HashMap::new(),
-                            body: None,
-                            should_compress: false,
-                        }
-                    }
+            }
+        }
+    }
+
+    fn handle_file_get(request: &Request, file_path: &Path) -> Response {
+        let version = request.version;
+
+        let metadata = match std::fs::metadata(file_path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                return Response {
+                    status_code: 404,
+                    version,
+                    headers: HashMap::new(),
+                    body: None,
+                    encoding: None,
+                    keep_alive: true,
                 }
-                _ => {
-                    eprintln!("unhandled request method");
+            }
+        };
+
+        if metadata.is_dir() {
+            return match Self::render_directory_listing(file_path, &request.path) {
+                Some(html) => {
+                    let mut headers = HashMap::new();
+                    headers.insert("Content-Type".into(), "text/html".to_string());
+                    headers.insert("Content-Length".into(), html.len().to_string());
                     Response {
-                        body: None,
-                        status_code: 500,
-                        version: request.version,
-                        headers: HashMap::new(),
-                        should_compress: false,
+                        status_code: 200,
+                        version,
+                        headers,
+                        body: Some(html.into_bytes()),
+                        encoding: None,
+                        keep_alive: true,
                     }
                 }
+                None => Response {
+                    status_code: 500,
+                    version,
+                    headers: HashMap::new(),
+                    body: None,
+                    encoding: None,
+                    keep_alive: true,
+                },
+            };
+        }
+
+        let bytes = match std::fs::read(file_path) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return Response {
+                    status_code: 404,
+                    version,
+                    headers: HashMap::new(),
+                    body: None,
+                    encoding: None,
+                    keep_alive: true,
+                }
+            }
+        };
+
+        let mtime_secs = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let etag = format!("W/\"{}-{}\"", metadata.len(), mtime_secs);
+
+        if Self::is_not_modified(request, &etag, mtime_secs) {
+            let mut headers = HashMap::new();
+            headers.insert("ETag".into(), etag);
+            headers.insert("Last-Modified".into(), Self::format_http_date(mtime_secs));
+            return Response {
+                status_code: 304,
+                version,
+                headers,
+                body: None,
+                encoding: None,
+                keep_alive: true,
+            };
+        }
+
+        let total = bytes.len() as u64;
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".into(), Self::mime_type_for(file_path).to_string());
+        headers.insert("ETag".into(), etag);
+        headers.insert("Last-Modified".into(), Self::format_http_date(mtime_secs));
+        headers.insert("Accept-Ranges".into(), "bytes".to_string());
+
+        match request.header("range").map(|value| Self::parse_range_header(value, total)) {
+            Some(RangeResult::Satisfiable(range)) => {
+                let slice = &bytes[range.start as usize..=range.end as usize];
+                headers.insert(
+                    "Content-Range".into(),
+                    format!("bytes {}-{}/{}", range.start, range.end, total),
+                );
+                headers.insert("Content-Length".into(), slice.len().to_string());
+                Response {
+                    status_code: 206,
+                    version,
+                    headers,
+                    body: Some(slice.to_vec()),
+                    encoding: None,
+                    keep_alive: true,
+                }
+            }
+            Some(RangeResult::Unsatisfiable) => {
+                let mut headers = HashMap::new();
+                headers.insert("Content-Range".into(), format!("bytes */{}", total));
+                Response {
+                    status_code: 416,
+                    version,
+                    headers,
+                    body: None,
+                    encoding: None,
+                    keep_alive: true,
+                }
+            }
+            Some(RangeResult::NotRequested) | None => {
+                headers.insert("Content-Length".into(), total.to_string());
+                Response {
+                    status_code: 200,
+                    version,
+                    headers,
+                    body: Some(bytes),
+                    encoding: None,
+                    keep_alive: true,
+                }
+            }
+        }
+    }
+
+    /// `If-None-Match` takes precedence over `If-Modified-Since` per
+    /// RFC 7232 §3.3.
+    fn is_not_modified(request: &Request, etag: &str, mtime_secs: u64) -> bool {
+        if let Some(if_none_match) = request.header("if-none-match") {
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate == etag);
+        }
+
+        if let Some(if_modified_since) = request.header("if-modified-since") {
+            if let Some(since) = Self::parse_http_date(if_modified_since) {
+                return mtime_secs <= since;
+            }
+        }
+
+        false
+    }
+
+    /// Parses a single `Range: bytes=...` header against a resource of
+    /// `total` bytes. Supports `start-end`, `start-` (to EOF), and `-suffix`
+    /// (last N bytes). Anything we don't recognize is treated as absent,
+    /// matching clients that fall back to a full response.
+    fn parse_range_header(value: &str, total: u64) -> RangeResult {
+        let Some(spec) = value.trim().strip_prefix("bytes=") else {
+            return RangeResult::NotRequested;
+        };
+        let Some((start_str, end_str)) = spec.split_once('-') else {
+            return RangeResult::NotRequested;
+        };
+
+        if total == 0 {
+            return RangeResult::Unsatisfiable;
+        }
+
+        let (start, end) = if start_str.is_empty() {
+            let Ok(suffix_len) = end_str.parse::<u64>() else {
+                return RangeResult::NotRequested;
+            };
+            if suffix_len == 0 {
+                return RangeResult::Unsatisfiable;
+            }
+            (total.saturating_sub(suffix_len), total - 1)
+        } else {
+            let Ok(start) = start_str.parse::<u64>() else {
+                return RangeResult::NotRequested;
+            };
+            let end = if end_str.is_empty() {
+                total - 1
+            } else {
+                match end_str.parse::<u64>() {
+                    Ok(end) => end.min(total - 1),
+                    Err(_) => return RangeResult::NotRequested,
+                }
+            };
+            (start, end)
+        };
+
+        if start > end || start >= total {
+            RangeResult::Unsatisfiable
+        } else {
+            RangeResult::Satisfiable(ByteRange { start, end })
+        }
+    }
+
+    fn handle_file_post(request: &Request, file_path: &Path) -> Response {
+        let mut resp_headers = HashMap::<String, String>::new();
+        resp_headers.insert("Content-Type".into(), "application/octet-stream".into());
+
+        let Some(body) = &request.body else {
+            return Response {
+                status_code: 500,
+                version: request.version,
+                headers: HashMap::new(),
+                body: None,
+                encoding: None,
+                keep_alive: true,
+            };
+        };
+
+        if let Ok(mut file) = File::create(file_path).context("Failed to create file") {
+            if file.write_all(body).context("Failed to write to file").is_ok() {
+                Response {
+                    status_code: 201,
+                    version: request.version,
+                    headers: resp_headers,
+                    body: Some(body.clone()),
+                    encoding: None,
+                    keep_alive: true,
+                }
+            } else {
+                eprintln!("failed to write file");
+                Response {
+                    status_code: 500,
+                    version: request.version,
+                    headers: HashMap::new(),
+                    body: None,
+                    encoding: None,
+                    keep_alive: true,
+                }
             }
         } else {
+            eprintln!("failed to create file");
             Response {
-                status_code: 404,
+                status_code: 500,
                 version: request.version,
                 headers: HashMap::new(),
                 body: None,
-                should_compress: false,
+                encoding: None,
+                keep_alive: true,
             }
         }
     }
 
+    /// Handles a WebSocket upgrade on `/ws`: perform the RFC 6455 handshake,
+    /// then take over the connection with a frame loop that bypasses the
+    /// normal `Response::to_bytes` write path entirely.
+    fn handle_websocket(
+        &self,
+        request: &Request,
+        mut tcp_stream: &TcpStream,
+        reader: &mut BufReader<&TcpStream>,
+    ) -> Result<()> {
+        let Some(handshake) = Self::websocket_handshake_response(request) else {
+            let mut bad_request = self.handle_bad_request();
+            bad_request.keep_alive = false;
+            tcp_stream
+                .write_all(&bad_request.to_bytes())
+                .context("Failed to write response")?;
+            return Ok(());
+        };
+
+        tcp_stream
+            .write_all(&handshake.to_bytes())
+            .context("Failed to write websocket handshake")?;
+
+        loop {
+            let frame = match Self::read_websocket_frame(reader) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(()),
+                Err(_) => return Ok(()),
+            };
+
+            match frame.opcode {
+                WebSocketOpcode::Text | WebSocketOpcode::Binary => {
+                    Self::write_websocket_frame(tcp_stream, frame.opcode, &frame.payload)
+                        .context("Failed to write websocket frame")?;
+                }
+                WebSocketOpcode::Ping => {
+                    Self::write_websocket_frame(tcp_stream, WebSocketOpcode::Pong, &frame.payload)
+                        .context("Failed to write websocket pong")?;
+                }
+                WebSocketOpcode::Pong => {}
+                WebSocketOpcode::Close => {
+                    Self::write_websocket_frame(tcp_stream, WebSocketOpcode::Close, &frame.payload)
+                        .context("Failed to write websocket close frame")?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Validates the handshake headers and, if acceptable, builds the
+    /// `101 Switching Protocols` response with the computed
+    /// `Sec-WebSocket-Accept` value. Returns `None` on anything that fails
+    /// to satisfy RFC 6455 (missing key, wrong version, missing
+    /// `Upgrade`/`Connection`).
+    fn websocket_handshake_response(request: &Request) -> Option<Response> {
+        let upgrade = request.header("upgrade")?.to_lowercase();
+        if upgrade != "websocket" {
+            return None;
+        }
+        let connection = request.header("connection")?.to_lowercase();
+        if !connection.split(',').map(str::trim).any(|token| token == "upgrade") {
+            return None;
+        }
+        if request.header("sec-websocket-version")? != "13" {
+            return None;
+        }
+        let key = request.header("sec-websocket-key")?;
+
+        let accept_input = format!("{}{}", key, WEBSOCKET_GUID);
+        let digest = sha1(accept_input.as_bytes());
+        let accept_value = base64_encode(&digest);
+
+        let mut headers = HashMap::new();
+        headers.insert("Upgrade".into(), "websocket".to_string());
+        headers.insert("Connection".into(), "Upgrade".to_string());
+        headers.insert("Sec-WebSocket-Accept".into(), accept_value);
+
+        Some(Response {
+            status_code: 101,
+            version: request.version,
+            headers,
+            body: None,
+            encoding: None,
+            keep_alive: true,
+        })
+    }
+
+    /// Reads one WebSocket frame, unmasking the payload (clients are
+    /// required to mask, per RFC 6455 §5.1). Returns `Ok(None)` on a clean
+    /// EOF. Fragmented messages (continuation frames) aren't supported.
+    fn read_websocket_frame(reader: &mut impl BufRead) -> Result<Option<WebSocketFrame>> {
+        let mut header = [0u8; 2];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err).context("Failed to read websocket frame header"),
+        }
+
+        let opcode = match header[0] & 0x0f {
+            0x1 => WebSocketOpcode::Text,
+            0x2 => WebSocketOpcode::Binary,
+            0x8 => WebSocketOpcode::Close,
+            0x9 => WebSocketOpcode::Ping,
+            0xA => WebSocketOpcode::Pong,
+            _ => return Ok(None),
+        };
+
+        let masked = header[1] & 0x80 != 0;
+        if !masked {
+            // RFC 6455 §5.1: a server MUST close the connection upon
+            // receiving an unmasked frame from a client.
+            return Ok(None);
+        }
+        let payload_len = match header[1] & 0x7f {
+            126 => {
+                let mut len_bytes = [0u8; 2];
+                reader
+                    .read_exact(&mut len_bytes)
+                    .context("Failed to read websocket extended length")?;
+                u16::from_be_bytes(len_bytes) as u64
+            }
+            127 => {
+                let mut len_bytes = [0u8; 8];
+                reader
+                    .read_exact(&mut len_bytes)
+                    .context("Failed to read websocket extended length")?;
+                u64::from_be_bytes(len_bytes)
+            }
+            len => len as u64,
+        };
+
+        if payload_len as usize > MAX_BODY_SIZE {
+            return Ok(None);
+        }
+
+        let mut mask = [0u8; 4];
+        reader
+            .read_exact(&mut mask)
+            .context("Failed to read websocket mask")?;
+
+        let mut payload = vec![0u8; payload_len as usize];
+        reader
+            .read_exact(&mut payload)
+            .context("Failed to read websocket payload")?;
+
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Some(WebSocketFrame { opcode, payload }))
+    }
+
+    /// Writes a single, unmasked, unfragmented server-to-client frame
+    /// (servers must not mask, per RFC 6455 §5.1).
+    fn write_websocket_frame(
+        mut tcp_stream: impl Write,
+        opcode: WebSocketOpcode,
+        payload: &[u8],
+    ) -> Result<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 10);
+        frame.push(0x80 | opcode.to_opcode_byte());
+
+        let len = payload.len();
+        if len < 126 {
+            frame.push(len as u8);
+        } else if len <= u16::MAX as usize {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+
+        frame.extend_from_slice(payload);
+        tcp_stream
+            .write_all(&frame)
+            .context("Failed to write websocket frame")
+    }
+
+    /// Resolves the extension-based `Content-Type` for a static file,
+    /// defaulting to `application/octet-stream` for anything unrecognized.
+    fn mime_type_for(file_path: &Path) -> &'static str {
+        let extension = file_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        extension
+            .as_deref()
+            .and_then(|ext| {
+                MIME_TYPES
+                    .iter()
+                    .find(|(known_ext, _)| *known_ext == ext)
+                    .map(|(_, mime)| *mime)
+            })
+            .unwrap_or("application/octet-stream")
+    }
+
+    /// Renders an HTML index of `dir`'s entries (name, size, modified time)
+    /// with links, or `None` if the directory couldn't be read.
+    fn render_directory_listing(dir: &Path, request_path: &str) -> Option<String> {
+        let mut entries: Vec<_> = std::fs::read_dir(dir).ok()?.filter_map(|e| e.ok()).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut rows = String::new();
+        for entry in entries {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            let href = if metadata.is_dir() {
+                format!("{}/", name)
+            } else {
+                name.clone()
+            };
+            let href = Self::html_escape(&href);
+            let name = Self::html_escape(&name);
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0);
+
+            rows.push_str(&format!(
+                "<tr><td><a href=\"{href}\">{name}</a></td><td>{}</td><td>{modified}</td></tr>\n",
+                metadata.len(),
+            ));
+        }
+
+        let request_path = Self::html_escape(request_path);
+        Some(format!(
+            "<!DOCTYPE html>\n<html>\n<head><title>Index of {request_path}</title></head>\n\
+             <body>\n<h1>Index of {request_path}</h1>\n<table>\n\
+             <tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n{rows}</table>\n</body>\n</html>\n",
+        ))
+    }
+
+    /// Escapes the characters that matter for both HTML text and
+    /// double-quoted attribute contexts, since entry names/hrefs land in
+    /// both above.
+    fn html_escape(value: &str) -> String {
+        value
+            .chars()
+            .map(|c| match c {
+                '&' => "&amp;".to_string(),
+                '<' => "&lt;".to_string(),
+                '>' => "&gt;".to_string(),
+                '"' => "&quot;".to_string(),
+                other => other.to_string(),
+            })
+            .collect()
+    }
+
+    /// Decodes `%XX` escapes; anything else (including malformed escapes)
+    /// passes through unchanged.
+    fn percent_decode(input: &str) -> String {
+        let bytes = input.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%'
+                && i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit()
+            {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                if let Some(value) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    decoded.push(value);
+                    i += 3;
+                    continue;
+                }
+            }
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+        String::from_utf8_lossy(&decoded).to_string()
+    }
+
+    /// Lexically resolves `.`/`..` segments and returns `None` if doing so
+    /// would climb above the root we're serving from (path traversal).
+    fn normalize_relative_path(relative_path: &str) -> Option<String> {
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in relative_path.split('/') {
+            match segment {
+                "" | "." => continue,
+                ".." => {
+                    segments.pop()?;
+                }
+                other => segments.push(other),
+            }
+        }
+        Some(segments.join("/"))
+    }
+
+    /// Catches what lexical normalization can't: a symlink inside `file_dir`
+    /// that resolves outside it. Canonicalizes the target (or, if it doesn't
+    /// exist yet, its parent — the `handle_file_post` create case) and
+    /// checks the result is still under the canonicalized root.
+    fn resolves_outside_root(file_dir: &str, file_path: &Path) -> bool {
+        let Ok(root) = std::fs::canonicalize(file_dir) else {
+            return true;
+        };
+        // `symlink_metadata` (unlike `exists`/`metadata`) doesn't follow a
+        // symlink, so a dangling symlink still counts as "the leaf is
+        // present" here and gets canonicalized below — where resolving its
+        // target fails and we correctly reject it, rather than silently
+        // falling back to checking its parent directory instead.
+        let to_check = if file_path.symlink_metadata().is_ok() {
+            file_path.to_path_buf()
+        } else {
+            match file_path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => return true,
+            }
+        };
+        match std::fs::canonicalize(&to_check) {
+            Ok(resolved) => !resolved.starts_with(&root),
+            Err(_) => true,
+        }
+    }
+
     fn handle_not_found(&self, request: &Request) -> Response {
         Response {
             status_code: 404,
             version: request.version,
             headers: HashMap::new(),
             body: None,
-            should_compress: false,
+            encoding: None,
+            keep_alive: true,
         }
     }
 
-    fn handle_internal_server_error(&self) -> Response {
+    /// A `/files/...` path that escapes `file_dir`, lexically or via a
+    /// symlink resolving outside it.
+    fn handle_forbidden(&self, request: &Request) -> Response {
         Response {
-            status_code: 500,
-            version: Version::Http1_1,
+            status_code: 403,
+            version: request.version,
             headers: HashMap::new(),
             body: None,
-            should_compress: false,
+            encoding: None,
+            keep_alive: true,
         }
     }
 
-    fn parse_request(input: &str) -> Result<Request, ParseError> {
-        let mut lines = input.lines().peekable();
+    /// A request we couldn't parse (bad framing, oversized/malformed body,
+    /// etc). This reflects a problem with what the client sent, not with us.
+    fn handle_bad_request(&self) -> Response {
+        Response {
+            status_code: 400,
+            version: Version::Http1_1,
+            headers: HashMap::new(),
+            body: None,
+            encoding: None,
+            keep_alive: true,
+        }
+    }
 
-        let req_line = lines.next().ok_or(ParseError::InvalidRequest)?;
-        let mut parts = req_line.split_whitespace();
+    /// Parses one request off `reader`, given its already-read start line.
+    /// Headers are read line by line and collapsed into
+    /// `HashMap<String, Vec<String>>` so repeated header names (e.g.
+    /// `Set-Cookie`) aren't silently overwritten, and the body is decoded
+    /// per `Content-Length` or `Transfer-Encoding: chunked`.
+    fn parse_request(start_line: &str, reader: &mut impl BufRead) -> Result<Request, ParseError> {
+        let mut parts = start_line.split_whitespace();
 
         let method = parts.next().ok_or(ParseError::InvalidRequest)?;
         let method = Self::parse_method(method)?;
 
-        let path = parts.next().ok_or(ParseError::InvalidRequest)?;
+        let path = parts.next().ok_or(ParseError::InvalidRequest)?.to_string();
 
         let version = parts.next().ok_or(ParseError::InvalidRequest)?;
         let version = Self::parse_version(version)?;
 
-        let mut headers = HashMap::new();
-        let mut body = None;
-
-        while let Some(line) = lines.next() {
-            if let Some((key, value)) = line.split_once(": ") {
-                headers.insert(key.to_lowercase().to_string(), value.to_string());
-            } else {
-                // Lazy me assuming the last line is the body.
-                body = Some(line.to_string());
+        let mut headers: HashMap<String, Vec<String>> = HashMap::new();
+        loop {
+            let mut line = String::new();
+            let bytes_read = reader
+                .read_line(&mut line)
+                .map_err(|_| ParseError::IncompleteHeaders)?;
+            if bytes_read == 0 {
+                return Err(ParseError::IncompleteHeaders);
             }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            let (key, value) = line.split_once(':').ok_or(ParseError::IncompleteHeaders)?;
+            headers
+                .entry(key.trim().to_lowercase())
+                .or_default()
+                .push(value.trim().to_string());
         }
 
+        let body = Self::read_body(reader, &headers)?;
+
         Ok(Request {
             method,
             path,
@@ -347,6 +1092,89 @@ impl HttpServer {
         })
     }
 
+    /// Reads the request body per `Transfer-Encoding`/`Content-Length`.
+    fn read_body(
+        reader: &mut impl BufRead,
+        headers: &HashMap<String, Vec<String>>,
+    ) -> Result<Option<Vec<u8>>, ParseError> {
+        let is_chunked = headers
+            .get("transfer-encoding")
+            .map(|values| values.iter().any(|v| v.to_lowercase().contains("chunked")))
+            .unwrap_or(false);
+
+        if is_chunked {
+            return Ok(Some(Self::read_chunked_body(reader)?));
+        }
+
+        let content_length = match headers.get("content-length").and_then(|v| v.first()) {
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| ParseError::InvalidContentLength)?,
+            None => 0,
+        };
+
+        if content_length == 0 {
+            return Ok(None);
+        }
+        if content_length > MAX_BODY_SIZE {
+            return Err(ParseError::BodyTooLarge);
+        }
+
+        let mut body = vec![0u8; content_length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|_| ParseError::IncompleteBody)?;
+        Ok(Some(body))
+    }
+
+    /// Decodes a `Transfer-Encoding: chunked` body: a hex chunk-size line,
+    /// that many bytes, a trailing CRLF, repeated until the `0` chunk,
+    /// followed by optional trailer headers up to the final blank line.
+    fn read_chunked_body(reader: &mut impl BufRead) -> Result<Vec<u8>, ParseError> {
+        let mut body = Vec::new();
+
+        loop {
+            let mut size_line = String::new();
+            reader
+                .read_line(&mut size_line)
+                .map_err(|_| ParseError::InvalidChunkSize)?;
+            let size_str = size_line.trim_end().split(';').next().unwrap_or("").trim();
+            let chunk_size = usize::from_str_radix(size_str, 16)
+                .map_err(|_| ParseError::InvalidChunkSize)?;
+
+            if chunk_size == 0 {
+                loop {
+                    let mut trailer_line = String::new();
+                    let bytes_read = reader
+                        .read_line(&mut trailer_line)
+                        .map_err(|_| ParseError::IncompleteBody)?;
+                    if bytes_read == 0 || trailer_line.trim_end_matches(['\r', '\n']).is_empty() {
+                        break;
+                    }
+                }
+                break;
+            }
+
+            if chunk_size > MAX_BODY_SIZE || body.len().saturating_add(chunk_size) > MAX_BODY_SIZE {
+                return Err(ParseError::BodyTooLarge);
+            }
+
+            let mut chunk = vec![0u8; chunk_size];
+            reader
+                .read_exact(&mut chunk)
+                .map_err(|_| ParseError::IncompleteBody)?;
+            body.extend_from_slice(&chunk);
+
+            let mut crlf = [0u8; 2];
+            reader
+                .read_exact(&mut crlf)
+                .map_err(|_| ParseError::IncompleteBody)?;
+        }
+
+        Ok(body)
+    }
+
     fn parse_method(method: &str) -> Result<Method, ParseError> {
         match method {
             "GET" => Ok(Method::Get),
@@ -366,15 +1194,112 @@ impl HttpServer {
             _ => Err(ParseError::InvalidVersion),
         }
     }
+
+    /// Formats a Unix timestamp as an RFC 1123 HTTP-date, e.g.
+    /// `Mon, 07 Nov 1994 08:49:37 GMT`. Implemented by hand (no date/time
+    /// dependency) using Howard Hinnant's civil-from-days algorithm.
+    fn format_http_date(unix_time: u64) -> String {
+        let days = (unix_time / 86400) as i64;
+        let secs_of_day = unix_time % 86400;
+        let (year, month, day) = Self::civil_from_days(days);
+        let weekday = Self::weekday_from_days(days);
+        let month_name = Self::month_name(month);
+        format!(
+            "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            weekday,
+            day,
+            month_name,
+            year,
+            secs_of_day / 3600,
+            (secs_of_day % 3600) / 60,
+            secs_of_day % 60,
+        )
+    }
+
+    /// Parses an RFC 1123 HTTP-date as emitted by `format_http_date`. Other
+    /// `If-Modified-Since` formats (RFC 850, asctime) are not accepted; a
+    /// client sending one simply won't get a 304, which is safe.
+    fn parse_http_date(value: &str) -> Option<u64> {
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        if parts.len() != 6 {
+            return None;
+        }
+        let day: u32 = parts[1].parse().ok()?;
+        let month = Self::month_number(parts[2])?;
+        let year: i64 = parts[3].parse().ok()?;
+        let mut time_parts = parts[4].splitn(3, ':');
+        let hour: u64 = time_parts.next()?.parse().ok()?;
+        let minute: u64 = time_parts.next()?.parse().ok()?;
+        let second: u64 = time_parts.next()?.parse().ok()?;
+
+        let days = Self::days_from_civil(year, month, day);
+        Some((days as u64) * 86400 + hour * 3600 + minute * 60 + second)
+    }
+
+    /// Howard Hinnant's `civil_from_days`: converts a day count since the
+    /// Unix epoch into a (year, month, day) civil date.
+    fn civil_from_days(z: i64) -> (i64, u32, u32) {
+        let z = z + 719468;
+        let era = if z >= 0 { z } else { z - 146096 } / 146097;
+        let doe = (z - era * 146097) as u64;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let y = yoe as i64 + era * 400;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        (if m <= 2 { y + 1 } else { y }, m, d)
+    }
+
+    /// Inverse of `civil_from_days`.
+    fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+        let y = if m <= 2 { y - 1 } else { y };
+        let era = if y >= 0 { y } else { y - 399 } / 400;
+        let yoe = (y - era * 400) as u64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) as u64 + 2) / 5 + d as u64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe as i64 - 719468
+    }
+
+    fn weekday_from_days(z: i64) -> &'static str {
+        const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        let idx = z.rem_euclid(7) as usize;
+        DAYS[idx]
+    }
+
+    fn month_name(month: u32) -> &'static str {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        MONTHS[(month - 1) as usize]
+    }
+
+    fn month_number(name: &str) -> Option<u32> {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        MONTHS.iter().position(|m| *m == name).map(|i| i as u32 + 1)
+    }
 }
 
 #[derive(Debug)]
-struct Request<'a> {
+struct Request {
     method: Method,
-    path: &'a str,
+    path: String,
     version: Version,
-    headers: HashMap<String, String>,
-    body: Option<String>,
+    headers: HashMap<String, Vec<String>>,
+    body: Option<Vec<u8>>,
+}
+
+impl Request {
+    /// Returns the first value for `name` (header names are stored
+    /// lowercased). Use `headers.get` directly for multi-value headers.
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
 }
 
 #[derive(Debug)]
@@ -382,26 +1307,45 @@ struct Response {
     status_code: u32,
     version: Version,
     headers: HashMap<String, String>,
-    body: Option<String>,
-    should_compress: bool,
+    body: Option<Vec<u8>>,
+    encoding: Option<Encoding>,
+    keep_alive: bool,
 }
 
 impl Response {
-    fn to_http_string(&self) -> String {
+    /// Serializes the full response (status line, headers, body) as raw
+    /// bytes. The body is kept as bytes end to end so binary payloads
+    /// (static files, compressed content) round-trip without corruption.
+    fn to_bytes(&self) -> Vec<u8> {
         let mut headers = self.headers.clone();
-        let body = if self.should_compress && self.body.is_some() {
-            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-            if let Some(body) = &self.body {
-                encoder.write_all(body.as_bytes()).unwrap_or_default();
-                if let Ok(compressed) = encoder.finish() {
-                    headers.insert("Content-Encoding".to_string(), "gzip".to_string());
-                    headers.insert("Content-Length".to_string(), compressed.len().to_string());
-                    String::from_utf8_lossy(&compressed).to_string()
-                } else {
-                    self.body.clone().unwrap_or_default()
+        // The 101 handshake response sets its own `Connection: Upgrade`
+        // header, which the usual keep-alive/close negotiation must not
+        // clobber.
+        if self.status_code != 101 {
+            headers.insert(
+                "Connection".to_string(),
+                (if self.keep_alive { "keep-alive" } else { "close" }).to_string(),
+            );
+        }
+        let body = if let (Some(encoding), Some(body)) = (self.encoding, &self.body) {
+            let compressed = match encoding {
+                Encoding::Gzip => {
+                    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(body).unwrap_or_default();
+                    encoder.finish()
                 }
+                Encoding::Deflate => {
+                    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(body).unwrap_or_default();
+                    encoder.finish()
+                }
+            };
+            if let Ok(compressed) = compressed {
+                headers.insert("Content-Encoding".to_string(), encoding.to_str().to_string());
+                headers.insert("Content-Length".to_string(), compressed.len().to_string());
+                compressed
             } else {
-                String::new()
+                self.body.clone().unwrap_or_default()
             }
         } else {
             self.body.clone().unwrap_or_default()
@@ -413,25 +1357,28 @@ impl Response {
             self.status_code,
             self.reason_phrase()
         );
-        let headers: String = headers
+        let header_lines: String = headers
             .iter()
             .map(|(key, value)| format!("{}: {}", key, value))
             .collect::<Vec<String>>()
             .join("\r\n");
 
-        format!(
-            "{}\r\n{}\r\n\r\n{}",
-            status_line,
-            headers,
-            body
-        )
+        let mut bytes = format!("{}\r\n{}\r\n\r\n", status_line, header_lines).into_bytes();
+        bytes.extend_from_slice(&body);
+        bytes
     }
 
     fn reason_phrase(&self) -> &str {
         match self.status_code {
+            101 => "Switching Protocols",
             200 => "OK",
             201 => "Created",
+            206 => "Partial Content",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            403 => "Forbidden",
             404 => "Not Found",
+            416 => "Range Not Satisfiable",
             500 => "Internal Server Error",
             _ => "Unknown Status",
         }
@@ -443,6 +1390,11 @@ enum ParseError {
     InvalidRequest,
     InvalidMethod,
     InvalidVersion,
+    IncompleteHeaders,
+    InvalidContentLength,
+    InvalidChunkSize,
+    IncompleteBody,
+    BodyTooLarge,
 }
 
 #[derive(Debug)]
@@ -462,7 +1414,7 @@ enum Version {
 }
 
 impl Version {
-    fn to_str(&self) -> &str {
+    fn to_str(self) -> &'static str {
         match self {
             Version::Http1_0 => "HTTP/1.0",
             Version::Http1_1 => "HTTP/1.1",
@@ -470,3 +1422,142 @@ impl Version {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn to_str(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+/// An inclusive byte range, as parsed from a `Range: bytes=...` header.
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+enum RangeResult {
+    Satisfiable(ByteRange),
+    Unsatisfiable,
+    NotRequested,
+}
+
+struct WebSocketFrame {
+    opcode: WebSocketOpcode,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WebSocketOpcode {
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl WebSocketOpcode {
+    fn to_opcode_byte(self) -> u8 {
+        match self {
+            WebSocketOpcode::Text => 0x1,
+            WebSocketOpcode::Binary => 0x2,
+            WebSocketOpcode::Close => 0x8,
+            WebSocketOpcode::Ping => 0x9,
+            WebSocketOpcode::Pong => 0xA,
+        }
+    }
+}
+
+/// A from-scratch SHA-1 (RFC 3174), since the only thing it's needed for
+/// here is the WebSocket handshake and pulling in a crate for one hash
+/// felt like overkill. Not for anything security-sensitive.
+fn sha1(input: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let bit_len = (input.len() as u64) * 8;
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_len.to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | ((!b) & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64 (RFC 4648) with padding, hand-rolled for the same
+/// reason as `sha1`.
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = *chunk.get(1).unwrap_or(&0);
+        let b2 = *chunk.get(2).unwrap_or(&0);
+
+        output.push(ALPHABET[(b0 >> 2) as usize] as char);
+        output.push(ALPHABET[(((b0 & 0x03) << 4) | (b1 >> 4)) as usize] as char);
+        output.push(if chunk.len() > 1 {
+            ALPHABET[(((b1 & 0x0f) << 2) | (b2 >> 6)) as usize] as char
+        } else {
+            '='
+        });
+        output.push(if chunk.len() > 2 {
+            ALPHABET[(b2 & 0x3f) as usize] as char
+        } else {
+            '='
+        });
+    }
+    output
+}